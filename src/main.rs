@@ -1,15 +1,17 @@
-use std::thread;
-use std::time::Duration;
-use env_logger::Target;
-use log::{debug, info, LevelFilter};
-use ril::{Font, Image, TextSegment};
-use ril::OverlayMode::Replace;
-use crate::pi::e_paper_ws_1in02::{BLACK_PIXEL, HEIGHT, Paper, WHITE_PIXEL, WIDTH};
-use crate::pi::font_load;
-
 mod pi;
 
+/// 演示程序依赖 `rppal` 便利构造器，仅在 `rppal` feature 开启时编译
+#[cfg(feature = "rppal")]
 fn main() {
+    use std::thread;
+    use std::time::Duration;
+    use env_logger::Target;
+    use log::{debug, LevelFilter};
+    use ril::{Font, Image, TextSegment};
+    use ril::OverlayMode::Replace;
+    use crate::pi::e_paper_ws_1in02::{BLACK_PIXEL, HEIGHT, RenderMode, WHITE_PIXEL, WIDTH};
+    use crate::pi::font_load;
+    use crate::pi::rppal_support::RppalPaper;
 
     env_logger::builder()
         .filter_level(LevelFilter::Debug)
@@ -18,7 +20,7 @@ fn main() {
 
     debug!("main start");
 
-    let mut paper = Paper::new();
+    let mut paper = RppalPaper::new_rppal();
     paper.on();
     paper.clear_screen();
     let font = Font::from_bytes(font_load::FONT, 12_f32).unwrap();
@@ -28,9 +30,14 @@ fn main() {
         .with_overlay_mode(Replace)
         .with_position(5, 5);
     img.draw(&text_draw);
-    paper.display(img);
+    paper.display(img, RenderMode::Threshold);
     thread::sleep(Duration::from_secs(2));
     paper.clear_screen();
 
     debug!("main end")
 }
+
+#[cfg(not(feature = "rppal"))]
+fn main() {
+    eprintln!("this demo binary only runs on Raspberry Pi Linux; build with `--features rppal`");
+}
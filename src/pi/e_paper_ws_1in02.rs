@@ -1,14 +1,10 @@
-use std::ops::Add;
-use std::time::{Duration, SystemTime};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::SpiDevice;
+use log::{debug, error, log_enabled, warn};
+use log::Level::Debug as LogDebug;
+use ril::{Image, L};
 
-use log::{debug, error, info, log_enabled, warn};
-use log::Level::Debug;
-use ril::{Font, Image, L, TextSegment};
-use ril::OverlayMode::Replace;
-use rppal::gpio::{Gpio, InputPin, Level, OutputPin};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
-
-use crate::pi::{font_load, pi_config};
 use crate::pi::buf_type_impl::Bytes;
 
 /// 高（因为是竖向一字节八个像素
@@ -16,7 +12,7 @@ pub const HEIGHT: u32 = 80;
 /// 宽（因为是竖向一字节八个像素
 pub const WIDTH: u32 = 128;
 /// 轮询忙状态的间隔
-const DEF_AWAIT_BUSY_MS: u64 = 50;
+const DEF_AWAIT_BUSY_MS: u32 = 50;
 /// 查询忙状态的命令
 const CMD_BZ_QUERY: &'static [u8] = &[0x71_u8];
 /// 显示命令
@@ -73,67 +69,168 @@ pub const WHITE_PIXEL: L = L::new(255_u8);
 const BUF_BLACK_ALL: &'static [u8] = &[0xff_u8; 1280];
 /// spi 发送的全白的缓冲
 const BUF_WHITE_ALL: &'static [u8] = &[0x00_u8; 1280];
+/// 打包后一帧的字节数，1280byte控制w=80 h=128 共80*128=10240像素，1bit一像素。
+/// 0x10/0x13 是该面板仅有的写入命令，均从头顺序写入，没有RAM地址窗口命令，故每次
+/// 下发都必须是完整的 BUF_LEN 字节，无法只发送变化的字节区间
+pub(crate) const BUF_LEN: usize = (HEIGHT * WIDTH / 8) as usize;
+/// 连续局部刷新的上限，超过后 `display_partial` 自动退化为全量刷新以消除重影
+const MAX_PARTIAL_REFRESHES: u32 = 20;
 
+/// `display_partial` 计算出的脏区域，以打包后缓冲区（[`BUF_LEN`]字节）的字节偏移表示，前闭后开
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRange {
+    pub start: usize,
+    pub end: usize,
+}
 
-/// 使当前线程sleep等待一定毫秒数，因为是sleep，所以不一定准确，该偏差属可接受范围内
-pub fn await_ms(ms: u64) {
-    std::thread::sleep(Duration::from_millis(ms))
+/// 对比新旧两帧打包缓冲，得到发生变化的字节区间，两帧完全一致时返回 None
+fn dirty_range(prev: &[u8; BUF_LEN], next: &[u8; BUF_LEN]) -> Option<DirtyRange> {
+    let start = prev.iter().zip(next.iter()).position(|(a, b)| a != b)?;
+    let end = prev.iter().zip(next.iter()).rposition(|(a, b)| a != b)? + 1;
+    Some(DirtyRange { start, end })
 }
 
-/// 将灰度图ril::Image转为spi发送的数据
+
+/// 灰度图转黑白时使用的渲染模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// 与 `BLACK_PIXEL` 精确比较，非黑即白，适合文字/线条图形
+    Threshold,
+    /// Floyd–Steinberg 误差扩散抖动，适合照片等连续色调图像
+    Dither,
+}
+
+/// 将灰度图ril::Image转为spi发送的数据，写入调用方提供的 `out`，输出不另行分配，
+/// 使 `out` 可以是 [`Paper`] 内部复用的常驻帧缓冲。`Threshold` 模式不做堆分配；
+/// `Dither` 模式每次调用会在堆上分配一份 `WIDTH*HEIGHT` 的亮度副本（`Vec<i32>`）
 /// WIDTH 墨水屏宽
 /// HEIGHT 墨水屏高
 /// Image 图片数据
-pub fn img_2_display_buf(img: &Image<L>) -> Vec<u8> {
-    let mut buf = vec![0xff_u8; (HEIGHT * WIDTH / 8) as usize];
+/// mode 黑白渲染模式，见 [`RenderMode`]
+pub fn img_2_display_buf(img: &Image<L>, mode: RenderMode, out: &mut [u8; BUF_LEN]) {
+    match mode {
+        RenderMode::Threshold => img_2_display_buf_threshold(img, out),
+        RenderMode::Dither => img_2_display_buf_dither(img, out),
+    }
+}
+
+fn img_2_display_buf_threshold(img: &Image<L>, out: &mut [u8; BUF_LEN]) {
+    out.fill(0xff_u8);
     for y in 0..HEIGHT {
         for x in 0..WIDTH {
             let new_x = y;
             let new_y = WIDTH - x - 1;
             if *(img.pixel(x, y)) == BLACK_PIXEL {
-                buf[((new_y * HEIGHT + new_x) / 8) as usize] &= !(0x80 >> (y % 8))
+                out[((new_y * HEIGHT + new_x) / 8) as usize] &= !(0x80 >> (y % 8))
             }
         }
     }
-    buf
 }
 
-pub struct Paper {
-    _gpio: Gpio,
-    rs_pin: OutputPin,
-    dc_pin: OutputPin,
-    bz_pin: InputPin,
-    pw_pin: OutputPin,
-    _spi: Spi,
+/// Floyd–Steinberg 误差扩散：在可变的亮度副本上按行优先顺序逐像素量化到{0,255}，
+/// 并将量化误差按 右7/16、左下3/16、下5/16、右下1/16 的权重扩散给尚未处理的邻居（越界邻居跳过），
+/// 使连续色调图像在二值屏幕上呈现出感知上的灰度
+fn img_2_display_buf_dither(img: &Image<L>, out: &mut [u8; BUF_LEN]) {
+    let w = WIDTH as usize;
+    let h = HEIGHT as usize;
+    let mut lum: Vec<i32> = (0..h).flat_map(|y| (0..w).map(move |x| (x, y)))
+        .map(|(x, y)| img.pixel(x as u32, y as u32).0 as i32)
+        .collect();
+
+    out.fill(0xff_u8);
+    for y in 0..h {
+        for x in 0..w {
+            let idx = y * w + x;
+            let old = lum[idx];
+            let new = if old < 128 { 0 } else { 255 };
+            let err = old - new;
+            if new == 0 {
+                let new_x = y as u32;
+                let new_y = WIDTH - x as u32 - 1;
+                out[((new_y * HEIGHT + new_x) / 8) as usize] &= !(0x80 >> (y as u32 % 8))
+            }
+
+            let mut spread = |dx: isize, dy: isize, weight: i32| {
+                let nx = x as isize + dx;
+                let ny = y as isize + dy;
+                if nx >= 0 && nx < w as isize && ny >= 0 && ny < h as isize {
+                    let n_idx = ny as usize * w + nx as usize;
+                    lum[n_idx] = (lum[n_idx] + err * weight / 16).clamp(0, 255);
+                }
+            };
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+}
+
+/// 驱动所依赖的一组 embedded-hal 1.0 外设，泛型化后不再绑定 rppal/Raspberry Pi，
+/// 任何实现了这些 trait 的板子（ESP32、STM32 等）都可以驱动该屏幕
+///
+/// - `SPI` 用于发送命令/数据
+/// - `RST` 复位引脚
+/// - `DC` 命令/数据选择引脚
+/// - `BUSY` 忙状态输入引脚
+/// - `PWR` 电源控制引脚
+/// - `DELAY` 延时提供者，取代 `std::thread::sleep`；驱动本身仍依赖 std
+///   （`String`、`Vec`、`ril`），并非 no_std
+pub struct Paper<SPI, RST, DC, BUSY, PWR, DELAY> {
+    spi: SPI,
+    rs_pin: RST,
+    dc_pin: DC,
+    bz_pin: BUSY,
+    pw_pin: PWR,
+    delay: DELAY,
+    /// 上一次成功提交到屏幕的打包帧，局部刷新据此与新帧做diff
+    last_buf: Option<[u8; BUF_LEN]>,
+    /// 上一次 display_partial 计算出的脏区域
+    last_dirty: Option<DirtyRange>,
+    /// 距离上一次全量刷新，已经发生的局部刷新次数
+    partial_count: u32,
+    /// 常驻的工作帧缓冲，渲染/diff复用同一块内存，避免每帧都在堆上分配 Vec
+    work_buf: [u8; BUF_LEN],
 }
 
-impl Paper {
-    pub fn new() -> Self {
-        let gpio = Gpio::new().unwrap();
+impl<SPI, RST, DC, BUSY, PWR, DELAY> Paper<SPI, RST, DC, BUSY, PWR, DELAY>
+    where
+        SPI: SpiDevice,
+        RST: OutputPin,
+        DC: OutputPin,
+        BUSY: InputPin,
+        PWR: OutputPin,
+        DELAY: DelayNs,
+{
+    /// 由调用方注入已初始化好的外设，构造驱动
+    pub fn new(spi: SPI, rs_pin: RST, dc_pin: DC, bz_pin: BUSY, pw_pin: PWR, delay: DELAY) -> Self {
         Paper {
-            rs_pin: gpio.get(pi_config::RST_PIN).unwrap().into_output(),
-            dc_pin: gpio.get(pi_config::DC_PIN).unwrap().into_output(),
-            bz_pin: gpio.get(pi_config::BUSY_PIN).unwrap().into_input(),
-            pw_pin: gpio.get(pi_config::PWR_PIN).unwrap().into_output(),
-            _gpio: gpio,
-            _spi: Spi::new(Bus::Spi0, SlaveSelect::Ss0, 4000000_u32, Mode::Mode0).unwrap(),
+            spi,
+            rs_pin,
+            dc_pin,
+            bz_pin,
+            pw_pin,
+            delay,
+            last_buf: None,
+            last_dirty: None,
+            partial_count: 0,
+            work_buf: [0xff_u8; BUF_LEN],
         }
     }
 
-
     /// 重置
     fn reset(&mut self) {
-        self.rs_pin.set_high();
-        await_ms(200);
-        self.rs_pin.set_low();
-        await_ms(2);
-        self.rs_pin.set_high();
-        await_ms(200);
+        self.rs_pin.set_high().ok();
+        self.delay.delay_ms(200);
+        self.rs_pin.set_low().ok();
+        self.delay.delay_ms(2);
+        self.rs_pin.set_high().ok();
+        self.delay.delay_ms(200);
     }
 
     fn spi_send(&mut self, buf: &[u8]) {
-        match self._spi.write(buf) {
-            Ok(_s_size) => {}
+        match self.spi.write(buf) {
+            Ok(_) => {}
             Err(e) => {
                 error!("spi send buf fail, err: {e:?}");
                 panic!("spi send panic!")
@@ -143,39 +240,39 @@ impl Paper {
 
     /// 发送命令
     fn send_cmd<T: Bytes>(&mut self, cmd: T) {
-        self.dc_pin.set_low();
+        self.dc_pin.set_low().ok();
         self.spi_send(cmd.bytes());
     }
 
     /// 发送数据
     fn send_data<T: Bytes>(&mut self, data: T) {
-        self.dc_pin.set_high();
+        self.dc_pin.set_high().ok();
         self.spi_send(data.bytes());
     }
 
-
-    ///当前线程阻塞，等待busy结束，或超时
-    fn await_busy(&mut self, timeout_opt: Option<Duration>) -> Result<(), String> {
+    ///当前线程阻塞，等待busy结束，或超时。timeout_ms为None时无限等待
+    fn await_busy(&mut self, timeout_ms: Option<u32>) -> Result<(), String> {
         if self.on_busy() {
-            match timeout_opt {
-                Some(dur) => {
-                    let dead_time = SystemTime::now().add(dur);
+            match timeout_ms {
+                Some(timeout_ms) => {
+                    let mut waited_ms = 0_u32;
                     while self.on_busy() {
-                        if log_enabled!(Debug) {
+                        if log_enabled!(LogDebug) {
                             debug!("busy timed await")
                         }
-                        if SystemTime::now() > dead_time {
+                        if waited_ms >= timeout_ms {
                             warn!("spi await busy timeout");
                             return Err(String::from("spi await busy timeout!"));
                         }
-                        await_ms(DEF_AWAIT_BUSY_MS);
+                        self.delay.delay_ms(DEF_AWAIT_BUSY_MS);
+                        waited_ms += DEF_AWAIT_BUSY_MS;
                     }
                 }
                 None => {
                     debug!("loop... busy timed await");
                     loop {
                         if self.on_busy() {
-                            await_ms(DEF_AWAIT_BUSY_MS);
+                            self.delay.delay_ms(DEF_AWAIT_BUSY_MS);
                         } else { break; }
                     }
                     debug!("break loop... busy timed await");
@@ -188,20 +285,16 @@ impl Paper {
     /// 查询墨水屏是否处于忙状态
     fn on_busy(&mut self) -> bool {
         self.send_cmd(CMD_BZ_QUERY);
-        match self.bz_pin.read() {
-            Level::High => { false }
-            Level::Low => { true }
-        }
+        self.bz_pin.is_low().unwrap_or(false)
     }
 
     /// 使其显示数据
     fn turn_on_display(&mut self) {
         self.send_cmd(CMD_TO_DISPLAY);
-        await_ms(10);
+        self.delay.delay_ms(10);
         self.await_busy(None).unwrap();
     }
 
-
     /// 向其发送lut涌动数据 全屏刷新的
     fn set_full_reg(&mut self) {
         self.send_cmd(CMD_LUT_W_REG);
@@ -217,11 +310,13 @@ impl Paper {
         self.send_data(PART_LUT_B_REG_DATA);
     }
 
-    /// 初始化墨水品
+    /// 初始化墨水品。`spi` 现在是 embedded-hal 的 `SpiDevice`，总线仅在每次
+    /// `send_cmd`/`send_data` 内部的 `write` 调用期间被占用，不会在调用间隙独占总线；
+    /// 电源引脚则贯穿 `on()`到`off()`/`drop()`之间保持高电平，因为屏幕的寄存器配置和
+    /// LUT 加载都依赖持续供电，无法按字节切换
     pub fn on(&mut self) {
-        // todo 后续要将 spi上电占用状态取消，只在发送时占用
         debug!("e-paper turn on...");
-        self.pw_pin.set_high(); // 上电
+        self.pw_pin.set_high().ok(); // 上电
         self.reset(); // 初始化
         self.send_cmd(0xD2);
         self.send_data(0x3F);
@@ -256,18 +351,85 @@ impl Paper {
         debug!("e-paper turn on.");
     }
 
-    /// 驱使其显示 1280byte即可控制其所有像素，w=80 h=128 共80*128=10240像素，每个像素用bit控制即可
-    /// 因为灰度为2
-    pub fn display(&mut self, img: Image<L>) {
-        debug!("e-paper starting display...");
+    /// 全量刷新 `work_buf`，记为下一次局部刷新diff的基准帧；与上一帧完全一致则跳过
+    fn display_work_buf(&mut self) {
+        if self.last_buf.as_ref() == Some(&self.work_buf) {
+            debug!("e-paper frame unchanged, skip refresh");
+            return;
+        }
+        let frame = self.work_buf;
         self.send_cmd(0x10);
         self.send_data(BUF_BLACK_ALL);
         self.send_cmd(0x13);
-        self.send_data(img_2_display_buf(&img)); // 转换并发送
+        self.send_data(&frame);
         self.turn_on_display();
+        self.last_buf = Some(frame);
+        self.last_dirty = None;
+        self.partial_count = 0;
+    }
+
+    /// 驱使其显示 1280byte即可控制其所有像素，w=80 h=128 共80*128=10240像素，每个像素用bit控制即可
+    /// 因为灰度为2。渲染直接写入常驻的 `work_buf`，输出帧不再每帧分配新 `Vec`
+    /// （`RenderMode::Dither` 仍会为亮度副本分配一次，见 [`img_2_display_buf`]）
+    pub fn display(&mut self, img: Image<L>, mode: RenderMode) {
+        debug!("e-paper starting display...");
+        img_2_display_buf(&img, mode, &mut self.work_buf);
+        self.display_work_buf();
         debug!("e-paper started display");
     }
 
+    /// 驱使其显示一个已经绘制好的 [`crate::pi::paper_buffer::PaperBuffer`]，
+    /// 用于搭配 `embedded-graphics` 绘制的图形/文字/图片，不必再经过 `ril::Image`
+    pub fn flush(&mut self, buf: &crate::pi::paper_buffer::PaperBuffer) {
+        debug!("e-paper starting flush...");
+        self.work_buf = *buf.as_bytes();
+        self.display_work_buf();
+        debug!("e-paper started flush");
+    }
+
+    /// 局部刷新是否安全：需要已有一帧可供diff的历史帧，且距离上一次全量刷新的
+    /// 局部刷新次数未超过 [`MAX_PARTIAL_REFRESHES`]，否则局部刷新会逐渐出现重影
+    pub fn is_partial_safe(&self) -> bool {
+        self.last_buf.is_some() && self.partial_count < MAX_PARTIAL_REFRESHES
+    }
+
+    /// 查询上一次 `display_partial` 计算出的脏区域；给调用方的诊断信息，驱动自身不用它裁剪传输
+    pub fn last_dirty_range(&self) -> Option<DirtyRange> {
+        self.last_dirty
+    }
+
+    /// 局部刷新：复用 `set_part_reg`，上一帧(OLD)写入 0x10、新一帧(NEW)写入 0x13，
+    /// 驱动内部据此只翻转变化的像素，免去全屏黑白冲刷（该面板没有RAM地址窗口命令，
+    /// 无法在SPI层面只发送变化字节区间，只能用此控制器级双缓冲做到"只刷新变化部分"，
+    /// 见 [`BUF_LEN`] 上的说明）。与上一帧完全一致则跳过；[`is_partial_safe`]为 false
+    /// 时退化为全量刷新
+    pub fn display_partial(&mut self, img: Image<L>, mode: RenderMode) {
+        img_2_display_buf(&img, mode, &mut self.work_buf);
+        if !self.is_partial_safe() {
+            debug!("e-paper display_partial falling back to full refresh");
+            self.display_work_buf();
+            return;
+        }
+        let prev_buf = self.last_buf.unwrap();
+        if prev_buf == self.work_buf {
+            debug!("e-paper frame unchanged, skip partial refresh");
+            return;
+        }
+        debug!("e-paper starting display_partial...");
+        let new_buf = self.work_buf;
+        self.last_dirty = dirty_range(&prev_buf, &new_buf);
+        self.set_part_reg();
+        self.send_cmd(0x10);
+        self.send_data(&prev_buf);
+        self.send_cmd(0x13);
+        self.send_data(&new_buf);
+        self.turn_on_display();
+        self.set_full_reg(); // 恢复全量LUT，使后续 display()/clear_screen() 行为不受影响
+        self.last_buf = Some(new_buf);
+        self.partial_count += 1;
+        debug!("e-paper started display_partial");
+    }
+
     /// 清屏
     pub fn clear_screen(&mut self) {
         debug!("e-paper starting clear_screen...");
@@ -276,12 +438,16 @@ impl Paper {
         self.send_cmd(0x13);
         self.send_data(BUF_BLACK_ALL);
         self.turn_on_display();
+        self.work_buf = [0xff_u8; BUF_LEN];
+        self.last_buf = Some(self.work_buf);
+        self.last_dirty = None;
+        self.partial_count = 0;
         debug!("e-paper started clear_screen");
     }
 
-    /// 关闭连接，释放占用的引脚等，然后拉低电源引脚电压
+    /// 关闭连接，然后拉低电源引脚电压。spi/gpio 的所有权随 `Paper` 的 drop 一并释放，
+    /// 不需要额外手动释放文件描述符
     fn off(&mut self) {
-        // todo 同 on方法一样，这里应该释放哪些占用的文件描述符
         debug!("e-paper turn off...");
         self.send_cmd(0x50);
         self.send_data(0xf7);
@@ -289,54 +455,22 @@ impl Paper {
         self.await_busy(None).unwrap();
         self.send_cmd(0x07);
         self.send_data(0xA5);
-        await_ms(2000);
-        self.pw_pin.set_low(); // 电源off
+        self.delay.delay_ms(2000);
+        self.pw_pin.set_low().ok(); // 电源off
         debug!("e-paper turn off.")
     }
 }
 
-impl Drop for Paper {
+impl<SPI, RST, DC, BUSY, PWR, DELAY> Drop for Paper<SPI, RST, DC, BUSY, PWR, DELAY>
+    where
+        SPI: SpiDevice,
+        RST: OutputPin,
+        DC: OutputPin,
+        BUSY: InputPin,
+        PWR: OutputPin,
+        DELAY: DelayNs,
+{
     fn drop(&mut self) {
         self.off()
     }
 }
-
-
-/// rppal 库 gpio使用 BCM编码，非物理编码
-///
-#[cfg(test)]
-mod test {
-    use std::thread;
-    use std::time::Duration;
-
-    use log::LevelFilter;
-    use ril::{Font, Image, TextSegment};
-    use ril::OverlayMode::Replace;
-
-    use crate::pi::e_paper_ws_1in02::{BLACK_PIXEL, HEIGHT, Paper, WHITE_PIXEL, WIDTH};
-    use crate::pi::font_load;
-
-    fn log_init() {
-        _ = env_logger::builder()
-            .filter_level(LevelFilter::Debug)
-            .is_test(true).try_init();
-    }
-
-    #[test]
-    fn test_display() {
-        log_init();
-        let mut paper = Paper::new();
-        paper.on();
-        paper.clear_screen();
-        let font = Font::from_bytes(font_load::FONT, 12_f32).unwrap();
-        /* 这里高为宽 宽为高 参考原微雪示例程序 方便后续转换 */
-        let mut img = Image::new(WIDTH, HEIGHT, WHITE_PIXEL);
-        let text_draw = TextSegment::new(&font, "test\ntest,test", BLACK_PIXEL)
-            .with_overlay_mode(Replace)
-            .with_position(5, 5);
-        img.draw(&text_draw);
-        paper.display(img);
-        thread::sleep(Duration::from_secs(2));
-        paper.clear_screen();
-    }
-}
\ No newline at end of file
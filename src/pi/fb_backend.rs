@@ -0,0 +1,120 @@
+//! Linux 用户态 framebuffer 后端，仅在 `framebuffer` feature 下编译
+//!
+//! 把面板注册为一块 mmap 出来的灰度内存区域（每像素一字节，尺寸 `WIDTH*HEIGHT`），
+//! 外部进程可以像写 `/dev/fbN` 一样直接打开同一个文件写入像素字节，不需要链接本 crate；
+//! [`FbBackend::run`] 在后台周期性地读取这块映射区域，按 [`crate::pi::e_paper_ws_1in02::img_2_display_buf`]
+//! 同款的列旋转打包后经 [`Paper`] 刷新到屏幕，整个过程复用已有的局部刷新与打包逻辑
+
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::SpiDevice;
+use memmap2::{MmapMut, MmapOptions};
+use ril::{Image, L};
+
+use crate::pi::e_paper_ws_1in02::{HEIGHT, Paper, RenderMode, WIDTH};
+
+/// framebuffer 后端的刷新策略
+pub struct FbConfig {
+    /// 轮询映射区域是否发生变化的周期
+    pub poll_interval: Duration,
+    /// 检测到变化后，需等待多久没有新变化才真正刷新到屏幕，用于合并高频写入、避免抖动
+    pub idle_coalesce: Duration,
+    /// 即便处于合法的局部刷新节奏内，也周期性强制走一次全量刷新以清理重影
+    pub force_full_refresh_every: Duration,
+    /// 映射区域灰度字节转黑白时使用的渲染模式，见 [`RenderMode`]。映射区域本就是
+    /// 任意程序写入的连续色调灰度数据，默认应选 `Dither` 而非 `Threshold`——后者只有
+    /// 精确等于 `BLACK_PIXEL` 的像素才会显黑，真实照片/抗锯齿文字几乎会被冲成空白
+    pub render_mode: RenderMode,
+}
+
+impl Default for FbConfig {
+    fn default() -> Self {
+        FbConfig {
+            poll_interval: Duration::from_millis(100),
+            idle_coalesce: Duration::from_millis(300),
+            force_full_refresh_every: Duration::from_secs(60),
+            render_mode: RenderMode::Dither,
+        }
+    }
+}
+
+/// 持有 mmap 映射区域与底层 [`Paper`] 的 framebuffer 后端
+pub struct FbBackend<SPI, RST, DC, BUSY, PWR, DELAY> {
+    paper: Paper<SPI, RST, DC, BUSY, PWR, DELAY>,
+    mmap: MmapMut,
+    config: FbConfig,
+}
+
+impl<SPI, RST, DC, BUSY, PWR, DELAY> FbBackend<SPI, RST, DC, BUSY, PWR, DELAY>
+    where
+        SPI: SpiDevice,
+        RST: OutputPin,
+        DC: OutputPin,
+        BUSY: InputPin,
+        PWR: OutputPin,
+        DELAY: DelayNs,
+{
+    /// 在 `path` 处创建/打开一个 `WIDTH*HEIGHT` 字节的文件并 mmap 之，每字节对应一个像素的灰度值
+    pub fn new(paper: Paper<SPI, RST, DC, BUSY, PWR, DELAY>, path: impl AsRef<Path>, config: FbConfig) -> std::io::Result<Self> {
+        let len = (WIDTH * HEIGHT) as u64;
+        let file = OpenOptions::new().read(true).write(true).create(true).open(path)?;
+        file.set_len(len)?;
+        let mmap = unsafe { MmapOptions::new().len(len as usize).map_mut(&file)? };
+        Ok(FbBackend { paper, mmap, config })
+    }
+
+    /// 把当前映射区域里的灰度字节转换为 `ril::Image`，供 `display`/`display_partial` 使用
+    fn snapshot_image(&self) -> Image<L> {
+        let mut img = Image::new(WIDTH, HEIGHT, L::new(255));
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let v = self.mmap[(y * WIDTH + x) as usize];
+                img.set_pixel(x, y, L::new(v));
+            }
+        }
+        img
+    }
+
+    /// 阻塞运行后台刷新循环：先 `on()` 唤醒面板，再按 `poll_interval` 轮询映射区域是否
+    /// 变化；变化后等待 `idle_coalesce` 窗口内不再有新变化才真正刷新一次，期间若距上次
+    /// 全量刷新已超过 `force_full_refresh_every`、或局部刷新暂不安全（见 `is_partial_safe`），
+    /// 则改走全量刷新以清理局部刷新积累的重影
+    pub fn run(&mut self) -> ! {
+        self.paper.on();
+
+        let mut last_checksum = checksum(&self.mmap);
+        let mut last_change_at = Instant::now();
+        let mut last_full_refresh_at = Instant::now();
+        let mut pending = false;
+        loop {
+            std::thread::sleep(self.config.poll_interval);
+
+            let current_checksum = checksum(&self.mmap);
+            if current_checksum != last_checksum {
+                last_checksum = current_checksum;
+                last_change_at = Instant::now();
+                pending = true;
+            }
+
+            if pending && last_change_at.elapsed() >= self.config.idle_coalesce {
+                let img = self.snapshot_image();
+                if last_full_refresh_at.elapsed() >= self.config.force_full_refresh_every || !self.paper.is_partial_safe() {
+                    self.paper.display(img, self.config.render_mode);
+                    last_full_refresh_at = Instant::now();
+                } else {
+                    self.paper.display_partial(img, self.config.render_mode);
+                }
+                pending = false;
+            }
+        }
+    }
+}
+
+/// 映射区域内容的简单指纹，用于判断两次轮询之间是否发生变化，避免每次都做逐字节比较
+fn checksum(buf: &[u8]) -> u64 {
+    buf.iter().fold(0xcbf29ce484222325_u64, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
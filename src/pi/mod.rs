@@ -0,0 +1,9 @@
+pub mod e_paper_ws_1in02;
+pub mod buf_type_impl;
+pub mod paper_buffer;
+pub mod font_load;
+pub mod pi_config;
+#[cfg(feature = "rppal")]
+pub mod rppal_support;
+#[cfg(feature = "framebuffer")]
+pub mod fb_backend;
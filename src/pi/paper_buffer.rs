@@ -0,0 +1,70 @@
+//! 基于 `embedded-graphics-core` 的帧缓冲，解耦渲染与 `ril`
+//!
+//! [`PaperBuffer`] 以 `BinaryColor` 实现 `DrawTarget`，内部保存的是
+//! `img_2_display_buf` 同款的按列旋转后的打包位图，填好后用 [`crate::pi::e_paper_ws_1in02::Paper::flush`]
+//! 直接刷新到屏幕，不必再经过 `ril::Image`
+
+use embedded_graphics_core::Pixel;
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::BinaryColor;
+
+use crate::pi::e_paper_ws_1in02::{BUF_LEN, HEIGHT, WIDTH};
+
+/// embedded-graphics 可绘制的帧缓冲，逻辑尺寸为 `WIDTH x HEIGHT`
+pub struct PaperBuffer {
+    buf: [u8; BUF_LEN],
+}
+
+impl PaperBuffer {
+    /// 新建一个全白（`BinaryColor::Off`）的缓冲
+    pub fn new() -> Self {
+        PaperBuffer { buf: [0xff_u8; BUF_LEN] }
+    }
+
+    /// 取得打包后的原始字节，供 [`crate::pi::e_paper_ws_1in02::Paper`] 直接刷新或diff
+    pub fn as_bytes(&self) -> &[u8; BUF_LEN] {
+        &self.buf
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: BinaryColor) {
+        let new_x = y;
+        let new_y = WIDTH - x - 1;
+        let idx = ((new_y * HEIGHT + new_x) / 8) as usize;
+        let mask = 0x80 >> (y % 8);
+        if color == BinaryColor::On {
+            self.buf[idx] &= !mask
+        } else {
+            self.buf[idx] |= mask
+        }
+    }
+}
+
+impl Default for PaperBuffer {
+    fn default() -> Self {
+        PaperBuffer::new()
+    }
+}
+
+impl OriginDimensions for PaperBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for PaperBuffer {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item=Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if coord.x >= 0 && (coord.x as u32) < WIDTH && coord.y >= 0 && (coord.y as u32) < HEIGHT {
+                self.set_pixel(coord.x as u32, coord.y as u32, color);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+//! `rppal` 便利构造器，仅在 Raspberry Pi Linux 上可用，通过 `rppal` feature 开启
+//!
+//! 将 `rppal` 的 gpio/spi 句柄包装为 embedded-hal 1.0 所需的
+//! `SpiDevice`/`OutputPin`/`InputPin`/`DelayNs`，从而复用泛型化后的 [`Paper`]
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+use rppal::gpio::{Gpio, InputPin, OutputPin};
+use rppal::spi::{Bus, Error as RppalSpiError, Mode, SlaveSelect, Spi};
+
+use crate::pi::e_paper_ws_1in02::Paper;
+use crate::pi::pi_config;
+
+/// rppal 自身不提供 embedded-hal 的 `SpiDevice`（设备级，含片选管理），
+/// 只包了一层给硬件片选的 `rppal::spi::Spi`（总线级）
+pub struct RppalSpiDevice(Spi);
+
+impl ErrorType for RppalSpiDevice {
+    type Error = RppalSpiError;
+}
+
+impl SpiDevice for RppalSpiDevice {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        for op in operations {
+            match op {
+                Operation::Write(buf) => { self.0.write(buf)?; }
+                Operation::Read(buf) => { self.0.read(buf)?; }
+                Operation::Transfer(read, write) => { self.0.transfer(read, write)?; }
+                Operation::TransferInPlace(buf) => { self.0.transfer_in_place(buf)?; }
+                Operation::DelayNs(ns) => { std::thread::sleep(std::time::Duration::from_nanos(*ns as u64)); }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 基于 `std::thread::sleep` 的延时提供者，仅用于有操作系统的 Raspberry Pi Linux 环境
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(std::time::Duration::from_nanos(ns as u64))
+    }
+}
+
+/// rppal 下的具体 Paper 类型别名，省去在调用处重复书写一长串泛型参数
+pub type RppalPaper = Paper<RppalSpiDevice, OutputPin, OutputPin, InputPin, OutputPin, StdDelay>;
+
+impl RppalPaper {
+    /// 便利构造器：按 [`pi_config`] 中的引脚配置，在 Raspberry Pi 上直接构造驱动
+    pub fn new_rppal() -> Self {
+        let gpio = Gpio::new().unwrap();
+        let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 4_000_000_u32, Mode::Mode0).unwrap();
+        Paper::new(
+            RppalSpiDevice(spi),
+            gpio.get(pi_config::RST_PIN).unwrap().into_output(),
+            gpio.get(pi_config::DC_PIN).unwrap().into_output(),
+            gpio.get(pi_config::BUSY_PIN).unwrap().into_input(),
+            gpio.get(pi_config::PWR_PIN).unwrap().into_output(),
+            StdDelay,
+        )
+    }
+}
+
+/// rppal 库 gpio使用 BCM编码，非物理编码
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::time::Duration;
+
+    use log::LevelFilter;
+    use ril::{Font, Image, TextSegment};
+    use ril::OverlayMode::Replace;
+
+    use crate::pi::e_paper_ws_1in02::{BLACK_PIXEL, HEIGHT, RenderMode, WHITE_PIXEL, WIDTH};
+    use crate::pi::font_load;
+    use crate::pi::rppal_support::RppalPaper;
+
+    fn log_init() {
+        _ = env_logger::builder()
+            .filter_level(LevelFilter::Debug)
+            .is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_display() {
+        log_init();
+        let mut paper = RppalPaper::new_rppal();
+        paper.on();
+        paper.clear_screen();
+        let font = Font::from_bytes(font_load::FONT, 12_f32).unwrap();
+        /* 这里高为宽 宽为高 参考原微雪示例程序 方便后续转换 */
+        let mut img = Image::new(WIDTH, HEIGHT, WHITE_PIXEL);
+        let text_draw = TextSegment::new(&font, "test\ntest,test", BLACK_PIXEL)
+            .with_overlay_mode(Replace)
+            .with_position(5, 5);
+        img.draw(&text_draw);
+        paper.display(img, RenderMode::Threshold);
+        thread::sleep(Duration::from_secs(2));
+        paper.clear_screen();
+    }
+}